@@ -0,0 +1,68 @@
+// Copyright The KCL Authors. All rights reserved.
+
+use anyhow::Result;
+use kclvm_ast::ast;
+
+use crate::codegen::error as kcl_error;
+
+/// The result type used by [`CodegenBackend`] methods.
+pub type CodegenResult<T> = Result<T>;
+
+/// The kind of artifact a [`CodegenBackend`] can dump to disk via
+/// [`CodegenBackend::emit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmitKind {
+    // Human-readable LLVM IR (`.ll`). Unsupported by non-LLVM backends.
+    LlvmIr,
+    // LLVM bitcode (`.bc`). Unsupported by non-LLVM backends.
+    Bitcode,
+    // A native object file (`.o`), suitable for linking into a larger program.
+    Object,
+    // Target assembly (`.s`).
+    Asm,
+    // A dlopen-able shared library, the artifact kind `kcl run` has always produced.
+    SharedLib,
+}
+
+/// Abstracts the three-pass compilation flow (predefine globals ->
+/// compile types -> walk modules) away from any single code generator,
+/// so alternative backends can be dropped in next to the default LLVM
+/// one.
+pub trait CodegenBackend<'ctx> {
+    /// Scan all the modules and allocate undefined values to global variable pointers.
+    fn predefine_global_vars(&self, modules: &'ctx [ast::Module]);
+
+    /// Build all the user-defined schema/rule types declared in `module`.
+    fn compile_types(&self, module: &'ctx ast::Module);
+
+    /// Generate the code for `module` itself.
+    fn walk_module(&self, module: &'ctx ast::Module) -> CodegenResult<()>;
+
+    /// Consume the backend and emit the final artifact bytes (e.g. a dlopen-able shared library).
+    fn finalize(self: Box<Self>) -> CodegenResult<Vec<u8>>;
+
+    /// Dump `kind` of this compilation's output to `output_path` without consuming the backend.
+    /// The default errors `kind` as unsupported; backends override it per [`EmitKind`] they produce.
+    fn emit(&self, kind: EmitKind, output_path: &str) -> CodegenResult<()> {
+        let _ = output_path;
+        Err(anyhow::anyhow!(
+            "this codegen backend does not support emitting {kind:?}"
+        ))
+    }
+
+    /// Compile AST Modules, which requires traversing three times.
+    /// 1. scan all possible global variables and allocate undefined values to global pointers.
+    /// 2. build all user-defined schema/rule types.
+    /// 3. generate code for the third time.
+    fn compile_ast_modules(&self, modules: &'ctx [ast::Module]) {
+        self.predefine_global_vars(modules);
+        for module in modules {
+            self.compile_types(module);
+        }
+        for module in modules {
+            self.walk_module(module)
+                .expect(kcl_error::COMPILE_ERROR_MSG);
+        }
+    }
+}
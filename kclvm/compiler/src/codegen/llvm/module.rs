@@ -1,9 +1,11 @@
 // Copyright The KCL Authors. All rights reserved.
 
+use anyhow::Result;
 use kclvm_ast::ast;
 use kclvm_ast::walker::TypedResultWalker;
 
 use super::context::LLVMCodeGenContext;
+use crate::codegen::backend::{CodegenBackend, EmitKind};
 use crate::codegen::error as kcl_error;
 use crate::codegen::traits::ValueMethods;
 use std::str;
@@ -123,3 +125,78 @@ impl<'ctx> LLVMCodeGenContext<'ctx> {
         }
     }
 }
+
+/// The default LLVM [`CodegenBackend`] implementation. It keeps the exact
+/// three-pass behavior `compile_ast_modules` has always had (including the
+/// per-module filename stack bookkeeping), while exposing the same passes
+/// through the trait so other backends (e.g. Cranelift) can be selected
+/// instead without touching the call site.
+impl<'ctx> CodegenBackend<'ctx> for LLVMCodeGenContext<'ctx> {
+    fn predefine_global_vars(&self, modules: &'ctx [ast::Module]) {
+        for ast_module in modules {
+            self.filename_stack
+                .borrow_mut()
+                .push(ast_module.filename.clone());
+            LLVMCodeGenContext::predefine_global_vars(self, ast_module);
+            self.filename_stack.borrow_mut().pop();
+        }
+    }
+
+    fn compile_types(&self, module: &'ctx ast::Module) {
+        self.filename_stack
+            .borrow_mut()
+            .push(module.filename.clone());
+        self.compile_module_import_and_types(module);
+        self.filename_stack.borrow_mut().pop();
+    }
+
+    fn walk_module(&self, module: &'ctx ast::Module) -> Result<()> {
+        self.filename_stack
+            .borrow_mut()
+            .push(module.filename.clone());
+        let result = TypedResultWalker::walk_module(self, module);
+        self.filename_stack.borrow_mut().pop();
+        result.map_err(|err| anyhow::anyhow!(err))
+    }
+
+    fn finalize(self: Box<Self>) -> Result<Vec<u8>> {
+        self.emit_to_memory_buffer()
+    }
+
+    fn emit(&self, kind: EmitKind, output_path: &str) -> Result<()> {
+        let module = self.module.borrow();
+        let path = std::path::Path::new(output_path);
+        match kind {
+            EmitKind::LlvmIr => module
+                .print_to_file(path)
+                .map_err(|err| anyhow::anyhow!(err.to_string())),
+            EmitKind::Bitcode => {
+                if module.write_bitcode_to_path(path) {
+                    Ok(())
+                } else {
+                    Err(anyhow::anyhow!(
+                        "failed to write LLVM bitcode to {output_path}"
+                    ))
+                }
+            }
+            EmitKind::Object => self
+                .target_machine
+                .write_to_file(&module, inkwell::targets::FileType::Object, path)
+                .map_err(|err| anyhow::anyhow!(err.to_string())),
+            EmitKind::Asm => self
+                .target_machine
+                .write_to_file(&module, inkwell::targets::FileType::Assembly, path)
+                .map_err(|err| anyhow::anyhow!(err.to_string())),
+            EmitKind::SharedLib => {
+                std::fs::write(path, self.emit_to_memory_buffer()?).map_err(Into::into)
+            }
+        }
+    }
+
+    /// The LLVM backend keeps its original, already-optimized three-pass
+    /// flow instead of the trait's default so existing callers observe no
+    /// behavior change.
+    fn compile_ast_modules(&self, modules: &'ctx [ast::Module]) {
+        LLVMCodeGenContext::compile_ast_modules(self, modules)
+    }
+}
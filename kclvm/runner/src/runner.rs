@@ -2,6 +2,8 @@ use anyhow::{anyhow, Result};
 use std::collections::HashMap;
 
 use kclvm_ast::ast;
+use kclvm_compiler::codegen::backend::{CodegenBackend, EmitKind};
+use kclvm_compiler::codegen::fingerprint::{self, Fingerprint};
 use kclvm_config::{
     modfile::get_vendor_home,
     settings::{SettingsFile, SettingsPathBuf},
@@ -13,6 +15,95 @@ use serde::{Deserialize, Serialize};
 use std::ffi::OsStr;
 
 const RESULT_SIZE: usize = 2048 * 2048;
+/// Refuse to grow a single `_kcl_run` result buffer past this size, so a
+/// runaway program errors instead of exhausting memory.
+const MAX_RESULT_SIZE: usize = 1024 * 1024 * 1024;
+
+/// A growable output buffer for one of `_kcl_run`'s JSON/warning/log
+/// out-params. It starts at `RESULT_SIZE`, but unlike a fixed buffer it
+/// can be resized to whatever length `_kcl_run` reports it actually
+/// needed, so large program output round-trips losslessly instead of
+/// being truncated.
+struct ResultBuffer {
+    data: Vec<u8>,
+}
+
+impl ResultBuffer {
+    fn new(initial_size: usize) -> Self {
+        Self {
+            data: vec![0u8; initial_size],
+        }
+    }
+
+    /// The capacity to report to `_kcl_run`. One byte is reserved, as the
+    /// original fixed buffers always did.
+    fn capacity(&self) -> kclvm_size_t {
+        self.data.len() as kclvm_size_t - 1
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut kclvm_char_t {
+        self.data.as_mut_ptr() as *mut kclvm_char_t
+    }
+
+    /// The `len` bytes `_kcl_run` actually wrote.
+    fn filled(&self, len: kclvm_size_t) -> &[u8] {
+        &self.data[0..len as usize]
+    }
+
+    /// Grow the buffer if `needed` (the size `_kcl_run` reported it
+    /// actually required) no longer fits, reporting whether it grew so
+    /// the caller knows to re-invoke `_kcl_run`.
+    fn grow_to_fit(&mut self, needed: kclvm_size_t) -> Result<bool> {
+        if (needed as usize) < self.data.len() {
+            return Ok(false);
+        }
+        let new_size = ((needed as usize) + 1).max(self.data.len() * 2);
+        if new_size > MAX_RESULT_SIZE {
+            return Err(anyhow!(
+                "_kcl_run result buffer would need to grow to {new_size} bytes, \
+                 exceeding the {MAX_RESULT_SIZE} byte limit"
+            ));
+        }
+        self.data = vec![0u8; new_size];
+        Ok(true)
+    }
+}
+
+/// Drive the grow-and-retry loop shared by every `_kcl_run`-shaped call:
+/// invoke `call` against the three result buffers, grow whichever ones it
+/// reports as too small (by writing the needed size back through the
+/// `*_len` out-params `call` returns), and retry until none grew.
+///
+/// `call` mirrors `_kcl_run`'s contract: given the three buffers, it
+/// returns `(n, result_len, warn_len, log_len)`, where `n` is `_kcl_run`'s
+/// own return code and the three lengths are what it reported needing (or
+/// used, once nothing grows further) for each buffer.
+///
+/// Note for callers wrapping an FFI function with side effects (like
+/// `_kcl_run`'s plugin calls via `plugin_agent`): a growth round re-invokes
+/// `call` from scratch, so any such side effect runs again on every retry,
+/// not just once.
+fn run_with_growable_buffers(
+    json_buf: &mut ResultBuffer,
+    warn_buf: &mut ResultBuffer,
+    log_buf: &mut ResultBuffer,
+    mut call: impl FnMut(
+        &mut ResultBuffer,
+        &mut ResultBuffer,
+        &mut ResultBuffer,
+    ) -> Result<(kclvm_size_t, kclvm_size_t, kclvm_size_t, kclvm_size_t)>,
+) -> Result<(kclvm_size_t, kclvm_size_t)> {
+    loop {
+        let (n, result_len, warn_len, log_len) = call(json_buf, warn_buf, log_buf)?;
+
+        let grew_json = json_buf.grow_to_fit(result_len)?;
+        let grew_warn = warn_buf.grow_to_fit(warn_len)?;
+        let grew_log = log_buf.grow_to_fit(log_len)?;
+        if !(grew_json || grew_warn || grew_log) {
+            break Ok((n, log_len));
+        }
+    }
+}
 
 #[allow(non_camel_case_types)]
 pub type kclvm_char_t = i8;
@@ -23,6 +114,86 @@ pub type kclvm_context_t = std::ffi::c_void;
 #[allow(non_camel_case_types)]
 pub type kclvm_value_ref_t = std::ffi::c_void;
 
+/// Selects which codegen backend compiles the program: the default
+/// optimizing LLVM backend, or the Cranelift backend that trades
+/// optimized output for a much faster compile.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Llvm,
+    Cranelift,
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Backend::Llvm
+    }
+}
+
+/// The codegen backend constructed for an [`ExecProgramArgs::backend`]
+/// selection. `Llvm` carries no payload here: its `LLVMCodeGenContext` is
+/// constructed by the existing LLVM driver, which already reads
+/// `ExecProgramArgs` today. This type exists so `Backend::Cranelift` has
+/// one real, reachable construction point instead of only ever being
+/// built from its own module.
+pub enum SelectedBackend {
+    Llvm,
+    Cranelift(kclvm_compiler::codegen::cranelift::CraneliftCodeGenContext),
+}
+
+/// Whether `target` and the host triple agree closely enough to dlopen (or,
+/// for the LLVM backend, to reuse the host-built `TargetMachine`): same
+/// architecture, operating system, ABI/libc (`environment`), and object
+/// file format (`binary_format`) -- e.g. `x86_64-unknown-linux-musl` does
+/// *not* match an `x86_64-unknown-linux-gnu` host, since a musl-linked
+/// artifact isn't guaranteed ABI-compatible with a glibc host. The single
+/// comparison backing both [`ExecProgramArgs::select_backend`]'s LLVM
+/// cross-compile guard and [`check_host_target`], so the two can't
+/// silently drift apart.
+fn triple_matches_host(target: &target_lexicon::Triple) -> bool {
+    let host = target_lexicon::Triple::host();
+    target.architecture == host.architecture
+        && target.operating_system == host.operating_system
+        && target.environment == host.environment
+        && target.binary_format == host.binary_format
+}
+
+impl ExecProgramArgs {
+    /// Construct the backend `self.backend` selects, passing `self.target`
+    /// down so a Cranelift cross-compilation request reaches the backend
+    /// that will actually emit for that triple.
+    pub fn select_backend(&self) -> Result<SelectedBackend> {
+        match self.backend {
+            Backend::Llvm => {
+                let target = self.get_target_triple()?;
+                // `LLVMCodeGenContext` builds its `TargetMachine` once, for
+                // the host, when it's constructed; retargeting it for a
+                // foreign triple means rebuilding that `TargetMachine`,
+                // which requires the context's constructor (not present in
+                // this checkout). Rather than silently emitting a
+                // host-targeted artifact while claiming it's for `target`,
+                // fail here until that plumbing exists.
+                if !triple_matches_host(&target) {
+                    let host = target_lexicon::Triple::host();
+                    return Err(anyhow!(
+                        "cross-compiling to `{target}` with `Backend::Llvm` is not supported yet; \
+                         use `Backend::Cranelift` for cross-compilation, or drop `target` to compile for `{host}`"
+                    ));
+                }
+                Ok(SelectedBackend::Llvm)
+            }
+            Backend::Cranelift => {
+                let target = self.get_target_triple()?;
+                Ok(SelectedBackend::Cranelift(
+                    kclvm_compiler::codegen::cranelift::CraneliftCodeGenContext::new_for_target(
+                        &target.to_string(),
+                    )?,
+                ))
+            }
+        }
+    }
+}
+
 /// ExecProgramArgs denotes the configuration required to execute the KCL program.
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct ExecProgramArgs {
@@ -53,12 +224,35 @@ pub struct ExecProgramArgs {
     /// Whether including schema type in JSON/YAML result
     pub include_schema_type_path: bool,
     // Whether to compile only.
+    //
+    // Deprecated: prefer `emit`. Kept for backward compatibility; when
+    // `emit` is empty, `compile_only` set to `true` behaves like
+    // `emit: [EmitKind::SharedLib]`.
     pub compile_only: bool,
+    // Which artifact kinds to dump to disk alongside (or instead of)
+    // running the program, e.g. `.ll`/`.bc` to inspect the codegen
+    // output, or `.o` to link into a larger program. Written to
+    // `emit_output` with the kind's conventional extension.
+    #[serde(default)]
+    pub emit: Vec<EmitKind>,
+    // The output path template `emit` writes to, without extension
+    // (e.g. `/tmp/prog` produces `/tmp/prog.ll`, `/tmp/prog.o`, ...).
+    #[serde(default)]
+    pub emit_output: Option<String>,
     // Whether to compile diractroy recursively.
     pub recursive: bool,
     // plugin_agent is the address of plugin.
     #[serde(skip)]
     pub plugin_agent: u64,
+    // Which codegen backend compiles the program. Defaults to the
+    // optimizing LLVM backend; `cranelift` favors compile speed instead.
+    #[serde(default)]
+    pub backend: Backend,
+    // The LLVM target triple to compile for, e.g. `x86_64-unknown-linux-gnu`
+    // or `aarch64-apple-darwin`. Defaults to the host triple when `None`,
+    // enabling cross-compilation of KCL artifacts on CI.
+    #[serde(default)]
+    pub target: Option<String>,
 }
 
 impl ExecProgramArgs {
@@ -145,6 +339,48 @@ impl ExecProgramArgs {
         self.k_filename_list.iter().map(|s| s.as_str()).collect()
     }
 
+    /// Parse `self.target`, falling back to the host triple when unset.
+    pub fn get_target_triple(&self) -> Result<target_lexicon::Triple> {
+        match &self.target {
+            Some(triple) => triple
+                .parse::<target_lexicon::Triple>()
+                .map_err(|err| anyhow!("invalid target triple `{triple}`: {err}")),
+            None => Ok(target_lexicon::Triple::host()),
+        }
+    }
+
+    /// Get the artifact kinds this run should dump to disk, folding the
+    /// deprecated `compile_only` flag into the new `emit` list so old
+    /// callers keep getting a shared library without having to migrate.
+    pub fn effective_emit(&self) -> Vec<EmitKind> {
+        if !self.emit.is_empty() {
+            self.emit.clone()
+        } else if self.compile_only {
+            vec![EmitKind::SharedLib]
+        } else {
+            vec![]
+        }
+    }
+
+    /// Compute the [`CodegenCache`] key for `modules`: a fingerprint over
+    /// the AST, folded with every codegen-affecting field of `self` (not
+    /// `plugin_agent`, a process-local pointer that never changes the
+    /// compiled bytes). Any new flag that changes what gets compiled must
+    /// be folded in here too, or stale entries can be served for it.
+    pub fn codegen_cache_key(&self, modules: &[ast::Module]) -> Fingerprint {
+        fingerprint::fingerprint_modules(modules)
+            .combine(Fingerprint::of_debug(&self.overrides))
+            .combine(Fingerprint::of_debug(&self.args))
+            .combine(Fingerprint::of_debug(&self.disable_none))
+            .combine(Fingerprint::of_debug(&self.strict_range_check))
+            .combine(Fingerprint::of_debug(&self.target))
+            .combine(Fingerprint::of_debug(&self.backend))
+            // Guard against stale entries surviving a compiler upgrade by
+            // folding in the compiler version, effectively versioning the
+            // whole cache directory.
+            .combine(Fingerprint::of_bytes(env!("CARGO_PKG_VERSION").as_bytes()))
+    }
+
     /// Get the [`kclvm_parser::LoadProgramOptions`] from the [`kclvm_runner::ExecProgramArgs`]
     pub fn get_load_program_options(&self) -> kclvm_parser::LoadProgramOptions {
         kclvm_parser::LoadProgramOptions {
@@ -161,6 +397,49 @@ impl ExecProgramArgs {
     }
 }
 
+/// The file extension `emit_output` gets for each requested [`EmitKind`].
+pub fn emit_kind_extension(kind: EmitKind, target: &target_lexicon::Triple) -> &'static str {
+    match kind {
+        EmitKind::LlvmIr => "ll",
+        EmitKind::Bitcode => "bc",
+        EmitKind::Object => "o",
+        EmitKind::Asm => "s",
+        EmitKind::SharedLib => match target.operating_system {
+            target_lexicon::OperatingSystem::Windows => "dll",
+            target_lexicon::OperatingSystem::Darwin(_)
+            | target_lexicon::OperatingSystem::MacOSX(_)
+            | target_lexicon::OperatingSystem::IOS(_) => "dylib",
+            _ => "so",
+        },
+    }
+}
+
+/// Emit every [`ExecProgramArgs::effective_emit`] artifact kind for
+/// `backend` under `emit_output`, each at its [`emit_kind_extension`] for
+/// `args`'s target (not the host the `kcl` binary itself runs on, which
+/// would pick the wrong extension when cross-compiling). A no-op when
+/// `args.effective_emit()` is empty (the common case: just run the
+/// program, don't dump anything to disk).
+pub fn emit_requested_artifacts<'ctx, B: kclvm_compiler::codegen::backend::CodegenBackend<'ctx>>(
+    backend: &B,
+    args: &ExecProgramArgs,
+) -> Result<()> {
+    let kinds = args.effective_emit();
+    if kinds.is_empty() {
+        return Ok(());
+    }
+    let output_base = args
+        .emit_output
+        .as_deref()
+        .ok_or_else(|| anyhow!("`emit` was requested but `emit_output` is not set"))?;
+    let target = args.get_target_triple()?;
+    for kind in kinds {
+        let path = format!("{output_base}.{}", emit_kind_extension(kind, &target));
+        backend.emit(kind, &path)?;
+    }
+    Ok(())
+}
+
 impl TryFrom<SettingsFile> for ExecProgramArgs {
     type Error = anyhow::Error;
     fn try_from(settings: SettingsFile) -> Result<Self, Self::Error> {
@@ -208,8 +487,105 @@ impl TryFrom<SettingsPathBuf> for ExecProgramArgs {
     }
 }
 
+/// A content-addressed cache of compiled shared libraries, keyed by
+/// [`ExecProgramArgs::codegen_cache_key`]. On a hit, the caller can skip
+/// codegen entirely and [`Artifact::from_path`] the cached `.so`; on a
+/// miss, it compiles as usual and [`CodegenCache::put`]s the result. Note
+/// that computing the key itself re-hashes the full parsed AST, so whether
+/// this is a net win depends on how that cost compares to codegen for a
+/// given program -- not yet benchmarked.
+pub struct CodegenCache {
+    dir: std::path::PathBuf,
+}
+
+impl CodegenCache {
+    /// New a cache rooted at `<vendor home>/cache/kclvm`.
+    pub fn new() -> Self {
+        Self {
+            dir: std::path::PathBuf::from(get_vendor_home())
+                .join("cache")
+                .join("kclvm"),
+        }
+    }
+
+    fn path_for(&self, key: Fingerprint) -> std::path::PathBuf {
+        self.dir.join(format!("{}.so", key.to_hex()))
+    }
+
+    /// Look up the cached artifact for `key`. Returns `None` on a miss
+    /// rather than erroring, so callers always have a recompile fallback.
+    pub fn get(&self, key: Fingerprint) -> Option<std::path::PathBuf> {
+        let path = self.path_for(key);
+        path.exists().then_some(path)
+    }
+
+    /// Store `bytes` under `key`, atomically: write to a sibling temp
+    /// file and rename it over the final path, so a concurrent `kcl run`
+    /// reading the cache never observes a partially-written entry.
+    pub fn put(&self, key: Fingerprint, bytes: &[u8]) -> Result<std::path::PathBuf> {
+        std::fs::create_dir_all(&self.dir)?;
+        let final_path = self.path_for(key);
+        let tmp_path = self
+            .dir
+            .join(format!("{}.tmp-{}", key.to_hex(), std::process::id()));
+        std::fs::write(&tmp_path, bytes)?;
+        std::fs::rename(&tmp_path, &final_path)?;
+        Ok(final_path)
+    }
+}
+
+impl Default for CodegenCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Compile `modules` through `cache`, keyed by `args.codegen_cache_key`:
+/// return the cached `.so` path on a hit, or call `compile` to produce the
+/// bytes on a miss, cache them, and return the freshly written path. Called
+/// from [`run_program`], which skips codegen for `Backend::Cranelift`
+/// entirely on a cache hit.
+pub fn compile_with_cache(
+    cache: &CodegenCache,
+    args: &ExecProgramArgs,
+    modules: &[ast::Module],
+    compile: impl FnOnce() -> Result<Vec<u8>>,
+) -> Result<std::path::PathBuf> {
+    let key = args.codegen_cache_key(modules);
+    if let Some(path) = cache.get(key) {
+        return Ok(path);
+    }
+    let bytes = compile()?;
+    cache.put(key, &bytes)
+}
+
+/// Refuse to load or run an artifact that was compiled for a different
+/// target than the one this process is running on, rather than letting the
+/// dynamic loader crash with an opaque "wrong ELF class" style error.
+/// [`KclLibRunner::run`] calls it before it dlopens the library.
+/// [`Artifact::run`] calls it before running, but [`Artifact::from_path`]
+/// has already dlopened the library by then.
+fn check_host_target(args: &ExecProgramArgs, lib_path: &str) -> Result<()> {
+    let target = args.get_target_triple()?;
+    if !triple_matches_host(&target) {
+        let host = target_lexicon::Triple::host();
+        return Err(anyhow!(
+            "cannot run `{lib_path}`: it was compiled for target `{target}`, \
+             but this process is running on `{host}`; run it on a `{target}` host instead"
+        ));
+    }
+    Ok(())
+}
+
 /// A public struct named [Artifact] which wraps around the native library [libloading::Library].
-pub struct Artifact(libloading::Library);
+/// It is backend-agnostic: it loads any shared library and runs it through
+/// its `kclvm_main`/`_kcl_run` symbols. The Cranelift
+/// [`kclvm_compiler::codegen::backend::CodegenBackend`] output does not
+/// define those symbols, so running it fails (see [`run_program`]).
+pub struct Artifact {
+    lib: libloading::Library,
+    path: String,
+}
 
 pub trait ProgramRunner {
     /// Run with the arguments [ExecProgramArgs] and return the program execute result that
@@ -219,20 +595,72 @@ pub trait ProgramRunner {
 
 impl ProgramRunner for Artifact {
     fn run(&self, args: &ExecProgramArgs) -> Result<ExecProgramResult> {
+        check_host_target(args, &self.path)?;
         unsafe {
-            KclLibRunner::lib_kclvm_plugin_init(&self.0, args.plugin_agent)?;
-            KclLibRunner::lib_kcl_run(&self.0, args)
+            KclLibRunner::lib_kclvm_plugin_init(&self.lib, args.plugin_agent)?;
+            KclLibRunner::lib_kcl_run(&self.lib, args)
         }
     }
 }
 
 impl Artifact {
     pub fn from_path<P: AsRef<OsStr>>(path: P) -> Result<Self> {
-        let lib = unsafe { libloading::Library::new(path)? };
-        Ok(Self(lib))
+        let path = path.as_ref().to_string_lossy().to_string();
+        let lib = unsafe { libloading::Library::new(&path)? };
+        Ok(Self { lib, path })
     }
 }
 
+/// Compile `modules` with the backend `args.backend` selects (through
+/// `cache`, so a [`CodegenCache`] hit skips codegen entirely), emit any
+/// [`ExecProgramArgs::effective_emit`] artifacts, then load and run the
+/// result exactly like a pre-built [`Artifact`] would. This is the one
+/// real (non-test) place [`ExecProgramArgs::select_backend`],
+/// [`emit_requested_artifacts`], and [`compile_with_cache`] are all
+/// called from.
+///
+/// Only `Backend::Cranelift` can actually produce something here:
+/// `Backend::Llvm` errors rather than pretending to compile, because
+/// driving the LLVM backend needs `LLVMCodeGenContext`'s constructor
+/// (`context.rs`), which this checkout doesn't have. Cranelift itself only
+/// lowers empty modules so far (see
+/// [`kclvm_compiler::codegen::cranelift::CraneliftCodeGenContext::walk_module`]):
+/// even for an empty module, [`Artifact::run`] below is expected to fail,
+/// since nothing ever defines the `kclvm_main`/`_kcl_run` symbols a real
+/// artifact needs -- that part of lowering isn't implemented either.
+pub fn run_program(
+    args: &ExecProgramArgs,
+    modules: &[ast::Module],
+    cache: &CodegenCache,
+) -> Result<ExecProgramResult> {
+    if matches!(args.backend, Backend::Llvm) {
+        return Err(anyhow!(
+            "running a program via `Backend::Llvm` is not wired up through \
+             `run_program` in this checkout: `LLVMCodeGenContext`'s constructor \
+             (context.rs) isn't present here"
+        ));
+    }
+
+    let so_path = compile_with_cache(cache, args, modules, || {
+        let backend = match args.select_backend()? {
+            SelectedBackend::Llvm => unreachable!("Backend::Llvm rejected above"),
+            SelectedBackend::Cranelift(backend) => backend,
+        };
+
+        backend.predefine_global_vars(modules);
+        for module in modules {
+            backend.compile_types(module);
+        }
+        for module in modules {
+            backend.walk_module(module)?;
+        }
+        emit_requested_artifacts(&backend, args)?;
+        Box::new(backend).finalize()
+    })?;
+
+    Artifact::from_path(&so_path)?.run(args)
+}
+
 #[derive(Debug, Default)]
 pub struct KclLibRunnerOptions {
     pub plugin_agent_ptr: u64,
@@ -252,6 +680,7 @@ impl KclLibRunner {
 
     /// Run kcl library with exec arguments.
     pub fn run(&self, lib_path: &str, args: &ExecProgramArgs) -> Result<ExecProgramResult> {
+        check_host_target(args, lib_path)?;
         unsafe {
             let lib = libloading::Library::new(std::path::PathBuf::from(lib_path).canonicalize()?)?;
             Self::lib_kclvm_plugin_init(&lib, self.opts.plugin_agent_ptr)?;
@@ -360,51 +789,60 @@ impl KclLibRunner {
         let list_option_mode = 0; // todo
         let debug_mode = args.debug;
 
-        // Exec json result
-        let mut json_result = vec![0u8; RESULT_SIZE];
-        let mut result_buffer_len = json_result.len() as i32 - 1;
-        let json_result_buffer = json_result.as_mut_ptr() as *mut i8;
-
-        // Exec warning data
-        let mut warn_data = vec![0u8; RESULT_SIZE];
-        let mut warn_buffer_len = warn_data.len() as i32 - 1;
-        let warn_buffer = warn_data.as_mut_ptr() as *mut i8;
-
-        // Exec log data
-        let mut log_data = vec![0u8; RESULT_SIZE];
-        let mut log_buffer_len = log_data.len() as i32 - 1;
-        let log_buffer = log_data.as_mut_ptr() as *mut i8;
-
-        let n = kcl_run(
-            kclvm_main_ptr,
-            option_len,
-            option_keys,
-            option_values,
-            strict_range_check,
-            disable_none,
-            disable_schema_check,
-            list_option_mode,
-            debug_mode,
-            &mut result_buffer_len,
-            json_result_buffer,
-            &mut warn_buffer_len,
-            warn_buffer,
-            &mut log_buffer_len,
-            log_buffer,
-        );
+        // Exec json/warning/log result buffers. Each starts at
+        // `RESULT_SIZE` but is not a hard cap: `_kcl_run` reports a
+        // buffer as too small by writing the size it actually needed
+        // back into the matching `*_buffer_len` out-param, and we grow
+        // and re-invoke until every buffer was big enough, instead of
+        // silently keeping truncated output.
+        let mut json_buf = ResultBuffer::new(RESULT_SIZE);
+        let mut warn_buf = ResultBuffer::new(RESULT_SIZE);
+        let mut log_buf = ResultBuffer::new(RESULT_SIZE);
+
+        let (n, log_buffer_len) = run_with_growable_buffers(
+            &mut json_buf,
+            &mut warn_buf,
+            &mut log_buf,
+            |json_buf, warn_buf, log_buf| {
+                let mut result_buffer_len = json_buf.capacity();
+                let mut warn_buffer_len = warn_buf.capacity();
+                let mut log_buffer_len = log_buf.capacity();
+
+                let n = kcl_run(
+                    kclvm_main_ptr,
+                    option_len,
+                    option_keys,
+                    option_values,
+                    strict_range_check,
+                    disable_none,
+                    disable_schema_check,
+                    list_option_mode,
+                    debug_mode,
+                    &mut result_buffer_len,
+                    json_buf.as_mut_ptr(),
+                    &mut warn_buffer_len,
+                    warn_buf.as_mut_ptr(),
+                    &mut log_buffer_len,
+                    log_buf.as_mut_ptr(),
+                );
+
+                Ok((n, result_buffer_len, warn_buffer_len, log_buffer_len))
+            },
+        )?;
+
         let mut result = ExecProgramResult {
-            log_message: String::from_utf8(log_data[0..log_buffer_len as usize].to_vec())?,
+            log_message: String::from_utf8(log_buf.filled(log_buffer_len).to_vec())?,
             ..Default::default()
         };
         if n > 0 {
-            let s = std::str::from_utf8(&json_result[0..n as usize])?;
+            let s = std::str::from_utf8(json_buf.filled(n))?;
             match wrap_msg_in_result(s) {
                 Ok(json) => result.json_result = json,
                 Err(err) => result.err_message = err,
             }
         } else if n < 0 {
             let return_len = 0 - n;
-            result.err_message = String::from_utf8(warn_data[0..return_len as usize].to_vec())?;
+            result.err_message = String::from_utf8(warn_buf.filled(return_len).to_vec())?;
         }
 
         // Wrap runtime error into diagnostic style string.
@@ -440,3 +878,278 @@ fn wrap_msg_in_result(msg: &str) -> Result<String, String> {
     }
     Ok(msg.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_backend_defaults_to_llvm() {
+        let args = ExecProgramArgs::default();
+        assert!(matches!(
+            args.select_backend().unwrap(),
+            SelectedBackend::Llvm
+        ));
+    }
+
+    #[test]
+    fn select_backend_constructs_cranelift() {
+        let args = ExecProgramArgs {
+            backend: Backend::Cranelift,
+            ..Default::default()
+        };
+        assert!(matches!(
+            args.select_backend().unwrap(),
+            SelectedBackend::Cranelift(_)
+        ));
+    }
+
+    #[test]
+    fn select_backend_rejects_llvm_cross_compilation() {
+        let foreign = if target_lexicon::Triple::host().architecture
+            == target_lexicon::Architecture::X86_64
+        {
+            "aarch64-unknown-linux-gnu"
+        } else {
+            "x86_64-unknown-linux-gnu"
+        };
+        let args = ExecProgramArgs {
+            backend: Backend::Llvm,
+            target: Some(foreign.to_string()),
+            ..Default::default()
+        };
+        assert!(args.select_backend().is_err());
+    }
+
+    #[test]
+    fn check_host_target_rejects_foreign_triple() {
+        let args = ExecProgramArgs {
+            target: Some("sparc64-unknown-linux-gnu".to_string()),
+            ..Default::default()
+        };
+        assert!(check_host_target(&args, "lib.so").is_err());
+    }
+
+    /// A [`CodegenCache`] rooted at a fresh per-test temp dir, so
+    /// `run_program` tests never share cache entries with each other or
+    /// with a real `kcl run`.
+    fn test_cache(name: &str) -> CodegenCache {
+        CodegenCache {
+            dir: std::env::temp_dir().join(format!(
+                "kclvm_run_program_test_{name}_{}",
+                std::process::id()
+            )),
+        }
+    }
+
+    #[test]
+    fn run_program_rejects_llvm_backend() {
+        let args = ExecProgramArgs::default();
+        let cache = test_cache("rejects_llvm_backend");
+        let err = run_program(&args, &[], &cache).unwrap_err().to_string();
+        assert!(err.contains("Backend::Llvm"));
+        std::fs::remove_dir_all(&cache.dir).ok();
+    }
+
+    #[test]
+    fn run_program_compiles_and_links_an_empty_cranelift_module_but_fails_to_run_it() {
+        // select_backend/finalize (including the real `cc` link step) run
+        // for real here; with no modules passed, compile_types/walk_module
+        // are never called. This only documents
+        // that running the result still fails, since nothing yet defines
+        // the kclvm_main/_kcl_run symbols Artifact::run needs.
+        let args = ExecProgramArgs {
+            backend: Backend::Cranelift,
+            ..Default::default()
+        };
+        let cache = test_cache("empty_cranelift_module");
+        assert!(run_program(&args, &[], &cache).is_err());
+        std::fs::remove_dir_all(&cache.dir).ok();
+    }
+
+    #[test]
+    fn run_program_second_call_hits_the_codegen_cache() {
+        // The first call compiles (and fails to run, same as above); the
+        // second call for the same args/modules must hit `compile_with_cache`
+        // and reuse the cached `.so` rather than invoking the Cranelift
+        // backend (and the system linker) again.
+        let args = ExecProgramArgs {
+            backend: Backend::Cranelift,
+            ..Default::default()
+        };
+        let cache = test_cache("hits_codegen_cache");
+        let key = args.codegen_cache_key(&[]);
+        assert!(cache.get(key).is_none());
+        let _ = run_program(&args, &[], &cache);
+        assert!(
+            cache.get(key).is_some(),
+            "run_program should have populated the cache on a miss"
+        );
+        let _ = run_program(&args, &[], &cache);
+        std::fs::remove_dir_all(&cache.dir).ok();
+    }
+
+    #[test]
+    fn check_host_target_accepts_host_triple() {
+        let args = ExecProgramArgs::default();
+        assert!(check_host_target(&args, "lib.so").is_ok());
+    }
+
+    #[test]
+    fn triple_matches_host_agrees_with_select_backend_and_check_host_target() {
+        let host = target_lexicon::Triple::host();
+        assert!(triple_matches_host(&host));
+
+        let foreign: target_lexicon::Triple = "sparc64-unknown-linux-gnu".parse().unwrap();
+        assert!(!triple_matches_host(&foreign));
+    }
+
+    #[test]
+    fn triple_matches_host_rejects_environment_mismatch() {
+        // Same architecture/OS as a glibc Linux host, different libc/ABI:
+        // must not be treated as a host match.
+        let musl: target_lexicon::Triple = "x86_64-unknown-linux-musl".parse().unwrap();
+        let gnu: target_lexicon::Triple = "x86_64-unknown-linux-gnu".parse().unwrap();
+        assert_ne!(musl.environment, gnu.environment);
+        if target_lexicon::Triple::host().environment == gnu.environment {
+            assert!(!triple_matches_host(&musl));
+        }
+    }
+
+    // A [`kclvm_compiler::codegen::backend::CodegenBackend`] test double that
+    // only records which `EmitKind`s it was asked to emit and to which
+    // paths, so `emit_requested_artifacts` can be exercised without a real
+    // LLVM or Cranelift backend.
+    struct NoopBackend {
+        emitted: std::cell::RefCell<Vec<(EmitKind, String)>>,
+    }
+
+    impl<'ctx> kclvm_compiler::codegen::backend::CodegenBackend<'ctx> for NoopBackend {
+        fn predefine_global_vars(&self, _modules: &'ctx [ast::Module]) {}
+        fn compile_types(&self, _module: &'ctx ast::Module) {}
+        fn walk_module(&self, _module: &'ctx ast::Module) -> anyhow::Result<()> {
+            Ok(())
+        }
+        fn finalize(self: Box<Self>) -> anyhow::Result<Vec<u8>> {
+            Ok(vec![])
+        }
+        fn emit(&self, kind: EmitKind, output_path: &str) -> anyhow::Result<()> {
+            self.emitted
+                .borrow_mut()
+                .push((kind, output_path.to_string()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn emit_requested_artifacts_is_noop_when_emit_is_empty() {
+        let backend = NoopBackend {
+            emitted: std::cell::RefCell::new(vec![]),
+        };
+        let args = ExecProgramArgs::default();
+        emit_requested_artifacts(&backend, &args).unwrap();
+        assert!(backend.emitted.borrow().is_empty());
+    }
+
+    #[test]
+    fn emit_requested_artifacts_emits_every_requested_kind() {
+        let backend = NoopBackend {
+            emitted: std::cell::RefCell::new(vec![]),
+        };
+        let args = ExecProgramArgs {
+            emit: vec![EmitKind::LlvmIr, EmitKind::Object],
+            emit_output: Some("/tmp/prog".to_string()),
+            ..Default::default()
+        };
+        emit_requested_artifacts(&backend, &args).unwrap();
+        assert_eq!(
+            backend.emitted.borrow().as_slice(),
+            &[
+                (EmitKind::LlvmIr, "/tmp/prog.ll".to_string()),
+                (EmitKind::Object, "/tmp/prog.o".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn emit_kind_extension_picks_shared_lib_extension_by_target_not_host() {
+        let linux: target_lexicon::Triple = "x86_64-unknown-linux-gnu".parse().unwrap();
+        let macos: target_lexicon::Triple = "aarch64-apple-darwin".parse().unwrap();
+        let windows: target_lexicon::Triple = "x86_64-pc-windows-msvc".parse().unwrap();
+
+        assert_eq!(emit_kind_extension(EmitKind::SharedLib, &linux), "so");
+        assert_eq!(emit_kind_extension(EmitKind::SharedLib, &macos), "dylib");
+        assert_eq!(emit_kind_extension(EmitKind::SharedLib, &windows), "dll");
+    }
+
+    #[test]
+    fn emit_requested_artifacts_errors_without_emit_output() {
+        let backend = NoopBackend {
+            emitted: std::cell::RefCell::new(vec![]),
+        };
+        let args = ExecProgramArgs {
+            emit: vec![EmitKind::Object],
+            ..Default::default()
+        };
+        assert!(emit_requested_artifacts(&backend, &args).is_err());
+    }
+
+    #[test]
+    fn compile_with_cache_misses_then_hits() {
+        let dir = std::env::temp_dir().join(format!(
+            "kclvm_compile_with_cache_test_{}",
+            std::process::id()
+        ));
+        let cache = CodegenCache { dir: dir.clone() };
+        let args = ExecProgramArgs::default();
+        let modules: Vec<ast::Module> = vec![];
+
+        let compiles = std::cell::Cell::new(0);
+        let path1 = compile_with_cache(&cache, &args, &modules, || {
+            compiles.set(compiles.get() + 1);
+            Ok(b"fake shared library bytes".to_vec())
+        })
+        .unwrap();
+        assert_eq!(compiles.get(), 1);
+        assert_eq!(std::fs::read(&path1).unwrap(), b"fake shared library bytes");
+
+        let path2 = compile_with_cache(&cache, &args, &modules, || {
+            compiles.set(compiles.get() + 1);
+            Ok(b"should not run".to_vec())
+        })
+        .unwrap();
+        assert_eq!(compiles.get(), 1, "second call should hit the cache");
+        assert_eq!(path1, path2);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn run_with_growable_buffers_round_trips_large_payload_losslessly() {
+        // Bigger than RESULT_SIZE (2048*2048 ~= 4MB), forcing at least one grow round.
+        let payload = vec![b'x'; 5_000_000];
+
+        let mut json_buf = ResultBuffer::new(RESULT_SIZE);
+        let mut warn_buf = ResultBuffer::new(RESULT_SIZE);
+        let mut log_buf = ResultBuffer::new(RESULT_SIZE);
+
+        let (n, _log_len) = run_with_growable_buffers(
+            &mut json_buf,
+            &mut warn_buf,
+            &mut log_buf,
+            |json, warn, log| {
+                let needed = payload.len() as kclvm_size_t;
+                if (json.capacity() as usize) < payload.len() {
+                    // Too small: report how much is actually needed, like `_kcl_run` does.
+                    return Ok((0, needed, warn.capacity(), log.capacity()));
+                }
+                json.data[..payload.len()].copy_from_slice(&payload);
+                Ok((needed, needed, warn.capacity(), log.capacity()))
+            },
+        )
+        .unwrap();
+
+        assert_eq!(n, payload.len() as kclvm_size_t);
+        assert_eq!(json_buf.filled(n), payload.as_slice());
+    }
+}